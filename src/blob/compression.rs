@@ -0,0 +1,124 @@
+//! Transparent at-rest compression for blob content.
+//!
+//! Every blob is written as a small header (codec + logical size) followed
+//! by the payload, compressed with that codec. Keeping the header even for
+//! [`Codec::None`] means [`decode`] never has to guess whether a given file
+//! predates this feature within the same store; `RUSTORE_DATA_PATH` pins a
+//! store to one codec the same way it pins a [`super::HashAlgo`].
+use super::config::persisted_setting;
+use super::errors::{BlobError, BlobErrorKind, Result};
+use std::{collections::HashMap, path::PathBuf, sync::Mutex};
+
+const MAGIC: &[u8; 4] = b"RCZ1";
+
+/// Compression codec applied to a blob's bytes before they hit disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    None,
+    Zstd,
+    Lz4,
+}
+
+impl Codec {
+    fn tag(self) -> u8 {
+        match self {
+            Codec::None => 0,
+            Codec::Zstd => 1,
+            Codec::Lz4 => 2,
+        }
+    }
+
+    fn from_tag(tag: u8) -> Result<Codec> {
+        match tag {
+            0 => Ok(Codec::None),
+            1 => Ok(Codec::Zstd),
+            2 => Ok(Codec::Lz4),
+            _ => Err(BlobError::Blob(BlobErrorKind::CorruptHeader)),
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Codec::None => "none",
+            Codec::Zstd => "zstd",
+            Codec::Lz4 => "lz4",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Codec> {
+        match value.trim() {
+            "none" => Some(Codec::None),
+            "zstd" => Some(Codec::Zstd),
+            "lz4" => Some(Codec::Lz4),
+            _ => None,
+        }
+    }
+
+    fn compress(self, data: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            Codec::None => Ok(data.to_vec()),
+            Codec::Zstd => zstd::stream::encode_all(data, 0).map_err(BlobError::IO),
+            Codec::Lz4 => lz4::block::compress(data, None, false).map_err(BlobError::IO),
+        }
+    }
+
+    fn decompress(self, data: &[u8], logical_size: usize) -> Result<Vec<u8>> {
+        match self {
+            Codec::None => Ok(data.to_vec()),
+            Codec::Zstd => zstd::stream::decode_all(data).map_err(BlobError::IO),
+            Codec::Lz4 => lz4::block::decompress(data, Some(logical_size as i32))
+                .map_err(BlobError::IO),
+        }
+    }
+}
+
+/// Returns the compression codec pinned to this store (`RUSTORE_COMPRESSION`,
+/// defaulting to `none`), the same way [`super::hash_algo`] pins a digest
+/// backend. Resolved once per `RUSTORE_DATA_PATH`, not once per process; see
+/// [`persisted_setting`]'s `cache` argument.
+pub fn compression_codec() -> Result<Codec> {
+    static CACHE: Mutex<HashMap<Option<PathBuf>, Codec>> = Mutex::new(HashMap::new());
+    persisted_setting(
+        &CACHE,
+        ".compression",
+        "RUSTORE_COMPRESSION",
+        Codec::parse,
+        Codec::as_str,
+        Codec::None,
+    )
+}
+
+/// Encodes `data` (the blob's *uncompressed* content) for on-disk storage
+/// under `codec`, prefixed with a header recording the codec and the
+/// logical (uncompressed) size.
+pub fn encode(data: &[u8], codec: Codec) -> Result<Vec<u8>> {
+    let mut out = Vec::from(&MAGIC[..]);
+    out.push(codec.tag());
+    out.extend_from_slice(&(data.len() as u64).to_le_bytes());
+    out.extend_from_slice(&codec.compress(data)?);
+    Ok(out)
+}
+
+/// Decodes bytes previously produced by [`encode`], returning the original
+/// (uncompressed) content.
+pub fn decode(on_disk: &[u8]) -> Result<Vec<u8>> {
+    Ok(decode_with_sizes(on_disk)?.0)
+}
+
+/// Like [`decode`], but returns `(content, codec, logical_size)`, the codec
+/// and logical size being read from the header, so callers (e.g.
+/// [`super::BlobMetadata`]) can report them without decoding twice.
+pub fn decode_with_sizes(on_disk: &[u8]) -> Result<(Vec<u8>, Codec, u64)> {
+    if on_disk.len() < MAGIC.len() + 1 + 8 || &on_disk[..MAGIC.len()] != MAGIC {
+        return Err(BlobError::Blob(BlobErrorKind::CorruptHeader));
+    }
+    let codec = Codec::from_tag(on_disk[MAGIC.len()])?;
+    let size_bytes: [u8; 8] = on_disk[MAGIC.len() + 1..MAGIC.len() + 9]
+        .try_into()
+        .map_err(|_| BlobError::Blob(BlobErrorKind::CorruptHeader))?;
+    let logical_size = u64::from_le_bytes(size_bytes);
+    let payload = &on_disk[MAGIC.len() + 9..];
+
+    let data = codec.decompress(payload, logical_size as usize)?;
+    Ok((data, codec, logical_size))
+}
@@ -0,0 +1,153 @@
+//! Store-level configuration pinned to a given `RUSTORE_DATA_PATH`.
+use super::errors::{BlobError, Result};
+use std::{collections::HashMap, env, fs, path::PathBuf, sync::Mutex};
+
+/// Digest algorithm used to compute `BlobRef`s for a store.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashAlgo {
+    Sha256,
+    Blake3,
+}
+
+impl HashAlgo {
+    /// Length, in hex characters, of a reference produced by this algorithm.
+    pub fn hex_len(self) -> usize {
+        match self {
+            HashAlgo::Sha256 => 64,
+            HashAlgo::Blake3 => 64,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            HashAlgo::Sha256 => "sha256",
+            HashAlgo::Blake3 => "blake3",
+        }
+    }
+
+    fn parse(value: &str) -> Option<HashAlgo> {
+        match value.trim() {
+            "sha256" => Some(HashAlgo::Sha256),
+            "blake3" => Some(HashAlgo::Blake3),
+            _ => None,
+        }
+    }
+}
+
+/// Returns the hash algorithm pinned to this store.
+///
+/// The first time this is called for a given `RUSTORE_DATA_PATH` with no
+/// `.hash_algo` file yet, `RUSTORE_HASH_ALGO` (defaulting to `sha256`) is
+/// used and persisted; every later call, in this process or any other,
+/// reads the persisted value regardless of the environment, so a store can
+/// never silently change algorithm underneath already-written blobs.
+///
+/// If `RUSTORE_DATA_PATH` itself is not set there is no store to pin
+/// anything to, so this just returns `RUSTORE_HASH_ALGO` (or the default)
+/// without touching the filesystem.
+///
+/// Resolved once per `RUSTORE_DATA_PATH` (see [`persisted_setting`]'s `cache`
+/// argument), not once per process: `BlobRef::new` calls this for every blob
+/// it constructs, which includes every chunk line of a manifest and every
+/// blob `blob_store::gc` visits, so it must not cost a filesystem read each
+/// time, but a process that opens more than one store (e.g. a test suite
+/// using per-test tempdirs) must still see each store's own setting.
+pub fn hash_algo() -> Result<HashAlgo> {
+    static CACHE: Mutex<HashMap<Option<PathBuf>, HashAlgo>> = Mutex::new(HashMap::new());
+    persisted_setting(
+        &CACHE,
+        ".hash_algo",
+        "RUSTORE_HASH_ALGO",
+        HashAlgo::parse,
+        HashAlgo::as_str,
+        HashAlgo::Sha256,
+    )
+}
+
+/// Reads a setting pinned to this store the first time it is requested,
+/// the same way [`hash_algo`] pins its digest backend: a `<data_path>/<file_name>`
+/// marker file wins over the environment once it exists, otherwise
+/// `env_var` (or `default`) is used and persisted for next time.
+///
+/// The resolved value is cached in `cache`, keyed by the current
+/// `RUSTORE_DATA_PATH` (or `None` if it isn't set), for as long as the
+/// process lives: only the first call for a *given store* touches the
+/// filesystem; every later call for that same store (in this process) is a
+/// plain memory read. A second store opened later in the same process gets
+/// its own cache entry rather than inheriting the first store's setting.
+/// Callers pass a `static Mutex<HashMap<..>>` scoped to their own setting.
+///
+/// Shared by every store-level setting ([`hash_algo`], and the
+/// `RUSTORE_COMPRESSION` codec) so they all behave the same way with
+/// respect to `RUSTORE_DATA_PATH` being unset.
+pub(super) fn persisted_setting<T: Copy>(
+    cache: &Mutex<HashMap<Option<PathBuf>, T>>,
+    file_name: &str,
+    env_var: &str,
+    parse: impl Fn(&str) -> Option<T>,
+    serialize: impl Fn(T) -> &'static str,
+    default: T,
+) -> Result<T> {
+    let data_path = env::var("RUSTORE_DATA_PATH").ok().map(PathBuf::from);
+
+    if let Some(&cached) = cache.lock().unwrap().get(&data_path) {
+        return Ok(cached);
+    }
+
+    let from_env = || env::var(env_var).ok().and_then(|value| parse(&value)).unwrap_or(default);
+
+    let value = match &data_path {
+        None => from_env(),
+        Some(base) => {
+            let path = base.join(file_name);
+            match fs::read_to_string(&path).ok().and_then(|existing| parse(&existing)) {
+                Some(value) => value,
+                None => {
+                    let value = from_env();
+                    if let Some(parent) = path.parent() {
+                        fs::create_dir_all(parent).map_err(BlobError::IO)?;
+                    }
+                    fs::write(&path, serialize(value)).map_err(BlobError::IO)?;
+                    value
+                }
+            }
+        }
+    };
+
+    cache.lock().unwrap().insert(data_path, value);
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A process that opens more than one store (e.g. this test function,
+    /// or a test suite using per-test tempdirs) must see each store's own
+    /// pinned setting, not whichever one happened to be resolved first.
+    #[test]
+    fn hash_algo_does_not_leak_between_stores() {
+        let store_a = env::temp_dir().join("rustore-config-test-a");
+        let store_b = env::temp_dir().join("rustore-config-test-b");
+        fs::create_dir_all(&store_a).unwrap();
+        fs::create_dir_all(&store_b).unwrap();
+        let _ = fs::remove_file(store_a.join(".hash_algo"));
+        let _ = fs::remove_file(store_b.join(".hash_algo"));
+
+        env::set_var("RUSTORE_DATA_PATH", &store_a);
+        env::set_var("RUSTORE_HASH_ALGO", "blake3");
+        assert_eq!(hash_algo().unwrap(), HashAlgo::Blake3);
+
+        env::set_var("RUSTORE_DATA_PATH", &store_b);
+        env::set_var("RUSTORE_HASH_ALGO", "sha256");
+        assert_eq!(hash_algo().unwrap(), HashAlgo::Sha256);
+
+        // Switching back to the first store must still return its own
+        // pinned algorithm, not the second store's.
+        env::set_var("RUSTORE_DATA_PATH", &store_a);
+        assert_eq!(hash_algo().unwrap(), HashAlgo::Blake3);
+
+        fs::remove_dir_all(&store_a).ok();
+        fs::remove_dir_all(&store_b).ok();
+    }
+}
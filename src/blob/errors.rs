@@ -0,0 +1,52 @@
+use std::{fmt, io};
+
+/// Specific kinds of errors that can occur while working with a [`super::BlobRef`].
+#[derive(Debug)]
+pub enum BlobErrorKind {
+    /// The supplied string is not a valid hex reference for the configured
+    /// hash algorithm: either its length doesn't match, or it contains a
+    /// character other than a lowercase hex digit.
+    InvalidRefLength,
+    /// The blob's directory exists but does not contain a file.
+    NotFound,
+    /// An offset passed to a range operation is not valid for the blob
+    /// (e.g. not aligned to the digest backend's leaf size).
+    InvalidRange,
+    /// An on-disk record could not be parsed: a compression header, a chunk
+    /// manifest, a POSIX metadata record or a tree manifest.
+    CorruptHeader,
+}
+
+/// Errors returned by the `blob` module.
+#[derive(Debug)]
+pub enum BlobError {
+    Blob(BlobErrorKind),
+    IO(io::Error),
+}
+
+impl fmt::Display for BlobError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlobError::Blob(BlobErrorKind::InvalidRefLength) => {
+                write!(f, "invalid blob reference")
+            }
+            BlobError::Blob(BlobErrorKind::NotFound) => write!(f, "blob not found"),
+            BlobError::Blob(BlobErrorKind::InvalidRange) => write!(f, "invalid byte range"),
+            BlobError::Blob(BlobErrorKind::CorruptHeader) => {
+                write!(f, "corrupt on-disk header or record")
+            }
+            BlobError::IO(err) => write!(f, "io error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for BlobError {}
+
+impl From<io::Error> for BlobError {
+    fn from(err: io::Error) -> Self {
+        BlobError::IO(err)
+    }
+}
+
+/// Convenience alias used throughout the `blob` module.
+pub type Result<T> = std::result::Result<T, BlobError>;
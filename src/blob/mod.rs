@@ -0,0 +1,12 @@
+//! Content-addressed storage of individual blobs.
+mod compression;
+mod config;
+mod errors;
+mod models;
+mod posix;
+
+pub use compression::{compression_codec, Codec};
+pub use config::{hash_algo, HashAlgo};
+pub use errors::{BlobError, BlobErrorKind, Result};
+pub use models::{BlobMetadata, BlobRef, Hasher};
+pub use posix::{NodeType, PosixMetadata};
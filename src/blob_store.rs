@@ -0,0 +1,343 @@
+//! Operations over the blob store as a whole, rather than a single blob:
+//! adding files (including special ones) and whole trees, pinning roots and
+//! reclaiming blobs that are no longer reachable from any of them.
+use crate::blob::{BlobError, BlobErrorKind, BlobRef, NodeType, PosixMetadata, Result};
+use crate::chunk;
+use std::{
+    collections::HashSet,
+    env,
+    fs::{self, File},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+};
+
+/// Adds the file, symlink, or special node at `path` to the store and
+/// returns both its `BlobRef` and the POSIX metadata captured from `path`.
+/// This is the one entry point that ties the two otherwise-independent
+/// `add` pipelines together: a `NodeType::Regular` file goes through
+/// [`chunk::add_file`] (content-defined chunking and cross-file dedup), and
+/// either way the caller gets back this file's `PosixMetadata` alongside
+/// the resulting blob — a chunk manifest or a single small blob.
+///
+/// A symlink's target is captured in its metadata instead of being
+/// followed, and FIFOs/block/character devices are recorded as a typed
+/// descriptor rather than opened; none of these has content of its own, so
+/// they are stored as a single empty blob rather than being chunked.
+///
+/// Content is content-addressed, so two different paths with identical
+/// content — most obviously two symlinks, or two FIFOs, which always hash
+/// to the same empty-content `BlobRef` — share a single `BlobRef` but must
+/// keep independent metadata. Callers must therefore track the returned
+/// `PosixMetadata` against the *path* it came from, e.g. via
+/// [`TreeManifest`], rather than persisting it keyed by the blob's content
+/// hash.
+pub fn add_file(path: &Path) -> Result<(BlobRef, PosixMetadata)> {
+    let posix = PosixMetadata::from_path(path)?;
+
+    let blob_ref = match posix.node_type {
+        NodeType::Regular => chunk::add_file(path)?,
+        NodeType::Symlink(_)
+        | NodeType::Fifo
+        | NodeType::BlockDevice { .. }
+        | NodeType::CharDevice { .. } => {
+            let empty = BlobRef::from_bytes(&[]);
+            if !empty.exists() {
+                empty.store(&[])?;
+            }
+            empty
+        }
+    };
+
+    Ok((blob_ref, posix))
+}
+
+/// One entry of a [`TreeManifest`]: a path relative to the tree's root,
+/// together with the `BlobRef` and `PosixMetadata` captured for it by
+/// [`add_file`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeEntry {
+    pub relative_path: PathBuf,
+    pub blob_ref: BlobRef,
+    pub posix: PosixMetadata,
+}
+
+/// A directory tree added to the store via [`TreeManifest::add_tree`]: one
+/// [`TreeEntry`] per file, symlink, or special node found under the root.
+///
+/// Metadata is kept here, indexed by path, rather than as a sidecar living
+/// inside each blob's content-addressed directory: two entries can (and,
+/// for symlinks and other special files, routinely do) share a single
+/// `BlobRef`, so metadata keyed by content hash would have the second entry
+/// silently overwrite the first's. Keeping the mapping in a manifest the
+/// caller holds, instead, lets every entry keep its own metadata regardless
+/// of what it shares a `BlobRef` with.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct TreeManifest {
+    pub entries: Vec<TreeEntry>,
+}
+
+const TREE_MANIFEST_MAGIC: &[u8] = b"RUSTORE_TREE_MANIFEST_V1\n";
+
+impl TreeManifest {
+    /// Recursively walks `root`, adding every file, symlink, and special
+    /// node under it to the store (see [`add_file`]) and recording each
+    /// one's path relative to `root`, `BlobRef`, and `PosixMetadata`.
+    /// Directories themselves are walked into, not added as entries; a
+    /// symlink to a directory is not followed and is recorded as a symlink.
+    pub fn add_tree(root: &Path) -> Result<TreeManifest> {
+        let mut entries = Vec::new();
+        walk_tree(root, root, &mut entries)?;
+        Ok(TreeManifest { entries })
+    }
+
+    /// Recreates every entry in this manifest under `dest`, preserving each
+    /// one's original relative path, node type, and POSIX metadata (see
+    /// [`BlobRef::restore_to`]).
+    pub fn restore_to(&self, dest: &Path) -> Result<()> {
+        for entry in &self.entries {
+            let target = dest.join(&entry.relative_path);
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent).map_err(BlobError::IO)?;
+            }
+            entry.blob_ref.restore_to(&target, &entry.posix)?;
+        }
+        Ok(())
+    }
+
+    /// Serializes this manifest to a small length-prefixed record per entry.
+    /// Plain newline-delimited lines (as used by `chunk::Manifest`) aren't
+    /// safe here since a relative path or an embedded `PosixMetadata` record
+    /// may itself contain newlines.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut out = Vec::from(TREE_MANIFEST_MAGIC);
+        write_len_prefixed(&mut out, self.entries.len().to_string().as_bytes());
+        for entry in &self.entries {
+            write_len_prefixed(&mut out, entry.relative_path.to_string_lossy().as_bytes());
+            write_len_prefixed(&mut out, entry.blob_ref.reference().as_bytes());
+            write_len_prefixed(&mut out, &entry.posix.serialize());
+        }
+        out
+    }
+
+    /// Parses a manifest previously produced by [`TreeManifest::serialize`].
+    pub fn parse(bytes: &[u8]) -> Result<TreeManifest> {
+        if !bytes.starts_with(TREE_MANIFEST_MAGIC) {
+            return Err(BlobError::Blob(BlobErrorKind::CorruptHeader));
+        }
+        let mut cursor = &bytes[TREE_MANIFEST_MAGIC.len()..];
+        let count: usize = parse_usize(read_len_prefixed(&mut cursor)?)?;
+
+        let mut entries = Vec::with_capacity(count);
+        for _ in 0..count {
+            let relative_path = PathBuf::from(
+                std::str::from_utf8(read_len_prefixed(&mut cursor)?)
+                    .map_err(|_| BlobError::Blob(BlobErrorKind::CorruptHeader))?,
+            );
+            let reference = std::str::from_utf8(read_len_prefixed(&mut cursor)?)
+                .map_err(|_| BlobError::Blob(BlobErrorKind::CorruptHeader))?;
+            let blob_ref = BlobRef::new(reference)?;
+            let posix = PosixMetadata::parse(read_len_prefixed(&mut cursor)?)?;
+            entries.push(TreeEntry {
+                relative_path,
+                blob_ref,
+                posix,
+            });
+        }
+        Ok(TreeManifest { entries })
+    }
+}
+
+fn walk_tree(root: &Path, current: &Path, entries: &mut Vec<TreeEntry>) -> Result<()> {
+    for dir_entry in fs::read_dir(current).map_err(BlobError::IO)? {
+        let dir_entry = dir_entry.map_err(BlobError::IO)?;
+        let path = dir_entry.path();
+        // `DirEntry::file_type` does not follow symlinks, so a symlink to a
+        // directory is reported as a symlink here, not descended into.
+        let file_type = dir_entry.file_type().map_err(BlobError::IO)?;
+
+        if file_type.is_dir() {
+            walk_tree(root, &path, entries)?;
+            continue;
+        }
+
+        let (blob_ref, posix) = add_file(&path)?;
+        let relative_path = path.strip_prefix(root).unwrap_or(&path).to_path_buf();
+        entries.push(TreeEntry {
+            relative_path,
+            blob_ref,
+            posix,
+        });
+    }
+    Ok(())
+}
+
+fn write_len_prefixed(out: &mut Vec<u8>, bytes: &[u8]) {
+    out.extend_from_slice(format!("{}\n", bytes.len()).as_bytes());
+    out.extend_from_slice(bytes);
+}
+
+fn read_len_prefixed<'a>(cursor: &mut &'a [u8]) -> Result<&'a [u8]> {
+    let newline = cursor
+        .iter()
+        .position(|&b| b == b'\n')
+        .ok_or(BlobError::Blob(BlobErrorKind::CorruptHeader))?;
+    let len = parse_usize(&cursor[..newline])?;
+    *cursor = &cursor[newline + 1..];
+    if cursor.len() < len {
+        return Err(BlobError::Blob(BlobErrorKind::CorruptHeader));
+    }
+    let (value, rest) = cursor.split_at(len);
+    *cursor = rest;
+    Ok(value)
+}
+
+fn parse_usize(bytes: &[u8]) -> Result<usize> {
+    std::str::from_utf8(bytes)
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .ok_or(BlobError::Blob(BlobErrorKind::CorruptHeader))
+}
+
+fn data_path() -> PathBuf {
+    PathBuf::from(env::var("RUSTORE_DATA_PATH").unwrap())
+}
+
+/// Path to the file persisting the set of pinned GC roots.
+fn roots_path() -> PathBuf {
+    data_path().join(".roots")
+}
+
+fn read_roots() -> Result<HashSet<String>> {
+    let path = roots_path();
+    if !path.exists() {
+        return Ok(HashSet::new());
+    }
+    BufReader::new(File::open(path).map_err(BlobError::IO)?)
+        .lines()
+        .map(|line| line.map_err(BlobError::IO))
+        .collect()
+}
+
+fn write_roots(roots: &HashSet<String>) -> Result<()> {
+    let mut file = File::create(roots_path()).map_err(BlobError::IO)?;
+    for root in roots {
+        writeln!(file, "{}", root).map_err(BlobError::IO)?;
+    }
+    Ok(())
+}
+
+/// Marks `blob_ref` as a GC root, persisting it to `.roots` so it (and
+/// anything it transitively references) survives [`gc`].
+pub fn pin(blob_ref: &BlobRef) -> Result<()> {
+    let mut roots = read_roots()?;
+    roots.insert(blob_ref.reference().to_string());
+    write_roots(&roots)
+}
+
+/// Removes `blob_ref` from the persisted set of GC roots.
+pub fn unpin(blob_ref: &BlobRef) -> Result<()> {
+    let mut roots = read_roots()?;
+    roots.remove(blob_ref.reference());
+    write_roots(&roots)
+}
+
+/// Returns the currently pinned GC roots.
+pub fn roots() -> Result<Vec<BlobRef>> {
+    read_roots()?.iter().map(|hex| BlobRef::new(hex)).collect()
+}
+
+fn subdir_names(dir: &Path) -> Result<Vec<String>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut names = Vec::new();
+    for entry in fs::read_dir(dir).map_err(BlobError::IO)? {
+        let entry = entry.map_err(BlobError::IO)?;
+        if entry.file_type().map_err(BlobError::IO)?.is_dir() {
+            names.push(entry.file_name().to_string_lossy().into_owned());
+        }
+    }
+    Ok(names)
+}
+
+/// Walks the `xx/yy/zz/rest` fan-out directories under `RUSTORE_DATA_PATH`
+/// and returns every blob currently present. This listing is taken as a
+/// single point-in-time snapshot *before* the mark phase runs, so a blob
+/// added concurrently while `gc` is executing is never swept: it simply
+/// isn't part of this snapshot.
+fn snapshot_blobs() -> Result<Vec<BlobRef>> {
+    let base = data_path();
+    let mut refs = Vec::new();
+    for xx in subdir_names(&base)? {
+        for yy in subdir_names(&base.join(&xx))? {
+            for zz in subdir_names(&base.join(&xx).join(&yy))? {
+                for rest in subdir_names(&base.join(&xx).join(&yy).join(&zz))? {
+                    if let Ok(blob_ref) = BlobRef::new(&format!("{}{}{}{}", xx, yy, zz, rest)) {
+                        refs.push(blob_ref);
+                    }
+                }
+            }
+        }
+    }
+    Ok(refs)
+}
+
+/// Runs mark-and-sweep garbage collection, reclaiming every blob in the
+/// snapshot taken at the start of the run that is not `roots` or reachable
+/// from `roots` via [`BlobRef::scan_refs`].
+///
+/// A blob whose content cannot be read or scanned is treated as reachable
+/// rather than risking collection of live data: it is marked before it is
+/// scanned, so a scan failure only means its own outgoing edges are not
+/// followed, not that the blob itself is swept.
+///
+/// In `dry_run` mode nothing is deleted; the blobs that would have been
+/// collected are still returned so a caller (e.g. the CLI's `--dry-run`
+/// flag) can list them.
+pub fn gc(roots: &[BlobRef], dry_run: bool) -> Result<Vec<BlobRef>> {
+    let snapshot = snapshot_blobs()?;
+    let present: HashSet<&str> = snapshot.iter().map(BlobRef::reference).collect();
+
+    let mut marked: HashSet<String> = HashSet::new();
+    let mut stack: Vec<BlobRef> = roots.to_vec();
+
+    while let Some(blob_ref) = stack.pop() {
+        if !marked.insert(blob_ref.reference().to_string()) {
+            continue;
+        }
+        if let Ok(refs) = blob_ref.scan_refs() {
+            for referenced in refs {
+                if present.contains(referenced.reference())
+                    && !marked.contains(referenced.reference())
+                {
+                    stack.push(referenced);
+                }
+            }
+        }
+    }
+
+    let unreachable: Vec<BlobRef> = snapshot
+        .into_iter()
+        .filter(|blob_ref| !marked.contains(blob_ref.reference()))
+        .collect();
+
+    if !dry_run {
+        for blob_ref in &unreachable {
+            blob_ref.delete()?;
+        }
+    }
+
+    Ok(unreachable)
+}
+
+/// Re-encodes every blob currently in the store with the store's *current*
+/// `RUSTORE_COMPRESSION` codec (see [`BlobRef::recompress`] for a single
+/// blob). Useful for migrating a store's existing blobs after changing
+/// that setting. Returns the number of blobs rewritten.
+pub fn recompress_all() -> Result<usize> {
+    let blobs = snapshot_blobs()?;
+    for blob_ref in &blobs {
+        blob_ref.recompress()?;
+    }
+    Ok(blobs.len())
+}
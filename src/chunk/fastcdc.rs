@@ -0,0 +1,146 @@
+//! FastCDC content-defined chunking with normalized chunking.
+//!
+//! This is a straightforward implementation of the algorithm described in
+//! "FastCDC: a Fast and Efficient Content-Defined Chunking Approach for Data
+//! Deduplication" (Xia et al., 2016): a rolling Gear hash is used to find cut
+//! points, with two masks (a stricter one below the target average size, a
+//! looser one above it) to keep the resulting chunk sizes close to the
+//! average without the bimodal distribution a single mask produces.
+
+/// Target average chunk size in bytes (8 MiB).
+pub const AVG_SIZE: usize = 8 * 1024 * 1024;
+/// Minimum chunk size: no cut point is considered before this many bytes.
+pub const MIN_SIZE: usize = AVG_SIZE / 4;
+/// Maximum chunk size: a cut is forced if no boundary is found before this.
+pub const MAX_SIZE: usize = AVG_SIZE * 2;
+
+// `mask_s` has more one-bits than `mask_l`, so it is harder to satisfy and
+// cuts less eagerly; it is used while the running chunk is still below
+// `AVG_SIZE`, biasing growth towards the average. `mask_l` has fewer
+// one-bits and is used once the chunk has grown past the average, biasing
+// towards an earlier cut.
+const MASK_S: u64 = 0xfffe_0000_0000_0000; // 15 one-bits
+const MASK_L: u64 = 0xffe0_0000_0000_0000; // 11 one-bits
+
+include!("gear_table.rs");
+
+/// Rolling Gear-hash cut-point finder.
+///
+/// Feed it bytes in order with [`Chunker::push`]; it returns `true` as soon
+/// as the current byte is a valid cut point. The caller is responsible for
+/// resetting the chunker (or creating a new one) once a chunk has been
+/// emitted.
+pub struct Chunker {
+    fingerprint: u64,
+    size: usize,
+}
+
+impl Default for Chunker {
+    fn default() -> Self {
+        Chunker {
+            fingerprint: 0,
+            size: 0,
+        }
+    }
+}
+
+impl Chunker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of bytes consumed since the last reset.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Feeds a single byte into the chunker, returning `true` if it falls on
+    /// a chunk boundary (including a forced cut at `MAX_SIZE`).
+    pub fn push(&mut self, byte: u8) -> bool {
+        self.size += 1;
+
+        if self.size < MIN_SIZE {
+            return false;
+        }
+        if self.size >= MAX_SIZE {
+            return true;
+        }
+
+        self.fingerprint = (self.fingerprint << 1).wrapping_add(GEAR[byte as usize]);
+        let mask = if self.size < AVG_SIZE { MASK_S } else { MASK_L };
+
+        self.fingerprint & mask == 0
+    }
+
+    /// Resets the chunker so it can be reused for the next chunk.
+    pub fn reset(&mut self) {
+        self.fingerprint = 0;
+        self.size = 0;
+    }
+}
+
+/// Splits `data` into content-defined chunks, returning the byte ranges.
+///
+/// Identical byte sequences always yield identical boundaries regardless of
+/// where they start, which is what makes chunks dedup-friendly across
+/// unrelated files.
+pub fn cut_points(data: &[u8]) -> Vec<std::ops::Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut chunker = Chunker::new();
+    let mut start = 0;
+
+    for (i, &byte) in data.iter().enumerate() {
+        if chunker.push(byte) {
+            ranges.push(start..i + 1);
+            start = i + 1;
+            chunker.reset();
+        }
+    }
+    if start < data.len() {
+        ranges.push(start..data.len());
+    }
+
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identical_content_chunks_identically_regardless_of_offset() {
+        let mut rng_state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = || {
+            rng_state ^= rng_state << 13;
+            rng_state ^= rng_state >> 7;
+            rng_state ^= rng_state << 17;
+            (rng_state % 256) as u8
+        };
+        let payload: Vec<u8> = (0..3 * AVG_SIZE).map(|_| next()).collect();
+
+        let mut prefixed = vec![0u8; 4096];
+        prefixed.extend_from_slice(&payload);
+
+        let direct: Vec<&[u8]> = cut_points(&payload).iter().map(|r| &payload[r.clone()]).collect();
+        let shifted: Vec<&[u8]> = cut_points(&prefixed).iter().map(|r| &prefixed[r.clone()]).collect();
+
+        // The chunker resyncs after the initial boundary disturbed by the
+        // prepended prefix, so the tail of chunks must match byte-for-byte.
+        let tail = 3;
+        assert!(direct.len() > tail && shifted.len() > tail);
+        assert_eq!(
+            &direct[direct.len() - tail..],
+            &shifted[shifted.len() - tail..]
+        );
+    }
+
+    #[test]
+    fn respects_min_and_max_size() {
+        let data = vec![0u8; MAX_SIZE * 3];
+        let ranges = cut_points(&data);
+        for r in &ranges[..ranges.len() - 1] {
+            assert!(r.len() >= MIN_SIZE);
+            assert!(r.len() <= MAX_SIZE);
+        }
+    }
+}
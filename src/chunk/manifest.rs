@@ -0,0 +1,60 @@
+//! On-disk representation of a chunked blob.
+use crate::blob::{BlobError, BlobErrorKind, BlobRef, Result};
+
+/// Every manifest blob starts with this byte sequence so `content()` can
+/// distinguish it from an ordinary, unchunked blob.
+pub const MAGIC: &[u8] = b"RUSTORE_MANIFEST_V1\n";
+
+/// An ordered list of chunk references that reassemble into the original
+/// file, plus its total (uncompressed) length.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Manifest {
+    pub chunks: Vec<BlobRef>,
+    pub length: u64,
+}
+
+impl Manifest {
+    pub fn new(chunks: Vec<BlobRef>, length: u64) -> Self {
+        Manifest { chunks, length }
+    }
+
+    /// Returns `true` if `bytes` looks like a manifest, i.e. starts with
+    /// [`MAGIC`].
+    pub fn is_manifest(bytes: &[u8]) -> bool {
+        bytes.starts_with(MAGIC)
+    }
+
+    /// Serializes the manifest as `MAGIC` followed by the total length and
+    /// one hex chunk reference per line.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut out = Vec::from(MAGIC);
+        out.extend_from_slice(format!("{}\n", self.length).as_bytes());
+        for chunk in &self.chunks {
+            out.extend_from_slice(chunk.reference().as_bytes());
+            out.push(b'\n');
+        }
+        out
+    }
+
+    /// Parses a manifest previously produced by [`Manifest::serialize`].
+    pub fn parse(bytes: &[u8]) -> Result<Manifest> {
+        if !Self::is_manifest(bytes) {
+            return Err(BlobError::Blob(BlobErrorKind::CorruptHeader));
+        }
+        let text = std::str::from_utf8(&bytes[MAGIC.len()..])
+            .map_err(|_| BlobError::Blob(BlobErrorKind::CorruptHeader))?;
+        let mut lines = text.lines();
+
+        let length: u64 = lines
+            .next()
+            .and_then(|line| line.parse().ok())
+            .ok_or(BlobError::Blob(BlobErrorKind::CorruptHeader))?;
+
+        let chunks = lines
+            .filter(|line| !line.is_empty())
+            .map(BlobRef::new)
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Manifest { chunks, length })
+    }
+}
@@ -0,0 +1,63 @@
+//! Content-defined chunking and cross-file deduplication for large blobs.
+//!
+//! Instead of hashing and storing a file as a single blob, [`add_file`]
+//! splits it into content-defined chunks with FastCDC, stores each chunk as
+//! an ordinary blob (so identical chunks across unrelated files are
+//! deduplicated automatically), and stores a small manifest blob listing the
+//! ordered chunk references. [`super::blob::BlobRef::content`] recognizes a
+//! manifest and transparently reassembles it.
+mod fastcdc;
+mod manifest;
+
+use crate::blob::{BlobError, BlobRef, Result};
+use std::{fs, io::Read, path::Path};
+
+pub use manifest::Manifest;
+
+/// Splits the file at `path` into content-defined chunks, stores each chunk
+/// (and the manifest referencing them) in the blob store, and returns a
+/// [`BlobRef`] for the manifest.
+///
+/// Chunks that already exist in the store (because some other file shares
+/// the same content) are not rewritten.
+pub fn add_file(path: &Path) -> Result<BlobRef> {
+    let mut file = fs::File::open(path)?;
+    let mut data = Vec::new();
+    file.read_to_end(&mut data)?;
+
+    let mut chunk_refs = Vec::new();
+    for range in fastcdc::cut_points(&data) {
+        let bytes = &data[range];
+        let chunk_ref = BlobRef::from_bytes(bytes);
+        if !chunk_ref.exists() {
+            chunk_ref.store(bytes)?;
+        }
+        chunk_refs.push(chunk_ref);
+    }
+
+    let manifest = Manifest::new(chunk_refs, data.len() as u64);
+    let serialized = manifest.serialize();
+    let manifest_ref = BlobRef::from_bytes(&serialized);
+    if !manifest_ref.exists() {
+        manifest_ref.store(&serialized)?;
+    }
+    Ok(manifest_ref)
+}
+
+/// Reassembles the original file content from a manifest, verifying each
+/// chunk's hash as it is read.
+pub fn reassemble(manifest: &Manifest) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(manifest.length as usize);
+    for chunk_ref in &manifest.chunks {
+        let bytes = chunk_ref.content()?;
+        let actual = BlobRef::from_bytes(&bytes);
+        if actual.reference() != chunk_ref.reference() {
+            return Err(BlobError::IO(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("chunk {} failed hash verification", chunk_ref),
+            )));
+        }
+        out.extend_from_slice(&bytes);
+    }
+    Ok(out)
+}
@@ -0,0 +1,3 @@
+pub mod blob;
+pub mod blob_store;
+pub mod chunk;